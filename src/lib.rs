@@ -1,8 +1,12 @@
 use std::{
 	thread,
+	pin::Pin,
+	collections::VecDeque,
+	panic::{ self, AssertUnwindSafe },
+	task::{ Context, Poll, Waker },
 	sync::{
 		Arc, Mutex, MutexGuard, Condvar,
-		atomic::{ AtomicBool, Ordering }
+		atomic::{ AtomicBool, AtomicUsize, Ordering }
 	},
 	time::{ Duration, Instant }
 };
@@ -24,10 +28,11 @@ pub enum State {
 
 /// An inner state object for a future
 struct Inner<T, U> {
-	payload: Mutex<(State, Option<T>)>,
+	payload: Mutex<(State, Option<T>, Option<Waker>)>,
 	cond_var: Condvar,
 	shared_state: Mutex<U>,
-	cancel_on_drop: AtomicBool
+	cancel_on_drop: AtomicBool,
+	on_cancel: Mutex<Vec<Box<dyn FnOnce() + Send>>>
 }
 unsafe impl<T, U> Sync for Inner<T, U> {}
 
@@ -38,10 +43,11 @@ impl<T, U> Future<T, U> {
 	/// Creates a new `Future<T, U>` with `shared_state` as shared-state
 	pub fn with_state(shared_state: U) -> Self {
 		Future(Arc::new(Inner {
-			payload: Mutex::new((State::Waiting, None)),
+			payload: Mutex::new((State::Waiting, None, None)),
 			cond_var: Condvar::new(),
 			shared_state: Mutex::new(shared_state),
-			cancel_on_drop: AtomicBool::new(true)
+			cancel_on_drop: AtomicBool::new(true),
+			on_cancel: Mutex::new(Vec::new())
 		}))
 	}
 	
@@ -53,9 +59,13 @@ impl<T, U> Future<T, U> {
 			Err(payload.0)?
 		}
 		
-		// Set result
-		*payload = (State::Ready, Some(result));
+		// Set result and wake both the blocking and the async consumer
+		payload.0 = State::Ready;
+		payload.1 = Some(result);
 		self.0.cond_var.notify_all();
+		if let Some(waker) = payload.2.take() {
+			waker.wake()
+		}
 		Ok(())
 	}
 	/// Cancels (poisons) the future
@@ -67,6 +77,55 @@ impl<T, U> Future<T, U> {
 		if payload.0 == State::Waiting {
 			payload.0 = State::Canceled;
 			self.0.cond_var.notify_all();
+			if let Some(waker) = payload.2.take() {
+				waker.wake()
+			}
+			drop(payload);
+			self.fire_on_cancel()
+		}
+	}
+	/// Blocks until the future leaves `State::Waiting` and returns `true` if it was canceled
+	///
+	/// This lets a running `job` park until its result is no longer wanted instead of busy-polling
+	/// `is_waiting`; it returns immediately if the future is already canceled (or otherwise set)
+	pub fn wait_canceled(&self) -> bool {
+		let mut payload = self.0.payload.lock().unwrap();
+		while payload.0 == State::Waiting {
+			payload = self.0.cond_var.wait(payload).unwrap()
+		}
+		payload.0 == State::Canceled
+	}
+	/// Blocks until the future leaves `State::Waiting` or the timeout occurres
+	///
+	/// Returns `true` if the future was canceled before the timeout, `false` otherwise
+	pub fn wait_canceled_timeout(&self, timeout: Duration) -> bool {
+		let timeout_point = Instant::now() + timeout;
+
+		let mut payload = self.0.payload.lock().unwrap();
+		while payload.0 == State::Waiting && Instant::now() < timeout_point {
+			payload = self.0.cond_var.wait_timeout(payload, time_remaining(timeout_point)).unwrap().0;
+		}
+		payload.0 == State::Canceled
+	}
+	/// Registers `f` to be called when the future is canceled
+	///
+	/// The callback fires from `cancel` (and the cancel-on-drop path in `Drop`); if the future is
+	/// already canceled, `f` is called immediately
+	pub fn on_cancel<F: FnOnce() + Send + 'static>(&self, f: F) {
+		// Hold the payload lock while registering so we don't race with a concurrent `cancel`
+		let payload = self.0.payload.lock().unwrap();
+		if payload.0 == State::Canceled {
+			drop(payload);
+			f()
+		} else {
+			self.0.on_cancel.lock().unwrap().push(Box::new(f))
+		}
+	}
+	/// Internal helper to drain and invoke the registered cancellation callbacks
+	fn fire_on_cancel(&self) {
+		let callbacks: Vec<_> = self.0.on_cancel.lock().unwrap().drain(..).collect();
+		for callback in callbacks {
+			callback()
 		}
 	}
 	/// Returns the future's state
@@ -133,6 +192,43 @@ impl<T, U> Future<T, U> {
 		modifier(&mut *shared_state_lock, parameter);
 	}
 	
+	/// Chains a follow-up job that applies `f` to this future's result
+	///
+	/// Returns a new future that becomes ready with `f(value)` once this future is ready; if this
+	/// future is canceled or already consumed, the returned future is canceled
+	pub fn map<V, F>(self, f: F) -> Future<V, ()>
+		where T: 'static + Send, U: 'static + Send, V: 'static + Send, F: FnOnce(T) -> V + Send + 'static
+	{
+		run_async(move |dst: Future<V>| match self.get() {
+			Ok(value) => { let _ = dst.set(f(value)); },
+			Err(_) => dst.cancel()
+		})
+	}
+	/// Chains a follow-up job that maps this future's result to another future and flattens it
+	///
+	/// A cancellation of either this future or the future returned by `f` cancels the returned future
+	pub fn and_then<V, W, F>(self, f: F) -> Future<V, ()>
+		where T: 'static + Send, U: 'static + Send, V: 'static + Send, W: 'static + Send,
+			F: FnOnce(T) -> Future<V, W> + Send + 'static
+	{
+		run_async(move |dst: Future<V>| match self.get() {
+			Ok(value) => match f(value).get() {
+				Ok(value) => { let _ = dst.set(value); },
+				Err(_) => dst.cancel()
+			},
+			Err(_) => dst.cancel()
+		})
+	}
+	/// Chains a follow-up job that applies `f` to this future's `Result`
+	///
+	/// Unlike `map` this also observes the `Canceled`/`Consumed` cases so they can be handled
+	pub fn then<V, F>(self, f: F) -> Future<V, ()>
+		where T: 'static + Send, U: 'static + Send, V: 'static + Send,
+			F: FnOnce(Result<T, State>) -> V + Send + 'static
+	{
+		run_async(move |dst: Future<V>| { let _ = dst.set(f(self.get())); })
+	}
+
 	/// Detaches the future so it won't be canceled if there is only one instance left
 	///
 	/// Useful if you either don't want that your future is ever canceled or if there's always only
@@ -141,8 +237,28 @@ impl<T, U> Future<T, U> {
 		self.0.cancel_on_drop.store(false, Ordering::Relaxed)
 	}
 	
+	/// Internal helper to block until a result is available and return a clone without consuming it
+	fn get_cloned(&self) -> Result<T, State> where T: Clone {
+		let mut payload = self.0.payload.lock().unwrap();
+		while payload.0 == State::Waiting {
+			payload = self.0.cond_var.wait(payload).unwrap()
+		}
+		match payload.1.as_ref() {
+			Some(value) if payload.0 == State::Ready => Ok(value.clone()),
+			_ => Err(payload.0)
+		}
+	}
+	/// Internal helper to inspect a ready value without blocking or consuming it
+	fn peek<R, F: FnOnce(&T) -> R>(&self, f: F) -> Option<R> {
+		let payload = self.0.payload.lock().unwrap();
+		match payload.1.as_ref() {
+			Some(value) if payload.0 == State::Ready => Some(f(value)),
+			_ => None
+		}
+	}
+
 	/// Internal helper to validate/update the future's state and get the payload
-	fn extract_payload(mut payload: MutexGuard<(State, Option<T>)>) -> Result<T, State> {
+	fn extract_payload(mut payload: MutexGuard<(State, Option<T>, Option<Waker>)>) -> Result<T, State> {
 		// Validate state
 		if payload.0 == State::Ready {
 			payload.0 = State::Consumed;
@@ -177,6 +293,32 @@ impl<T, U> Clone for Future<T, U> {
 }
 unsafe impl<T: Send, U: Send> Send for Future<T, U> {}
 unsafe impl<T, U> Sync for Future<T, U> {}
+impl<T, U> std::future::Future for Future<T, U> {
+	type Output = Result<T, State>;
+
+	/// Polls the future, driving the same state machine as the blocking `get`
+	///
+	/// If the future is ready it is consumed and `Poll::Ready(Ok(value))` is returned; if it has
+	/// been canceled `Poll::Ready(Err(State::Canceled))` is returned; otherwise the waker is stored
+	/// and `Poll::Pending` is returned until `set`/`cancel` wakes the task
+	fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+		let mut payload = self.0.payload.lock().unwrap();
+		match payload.0 {
+			State::Ready => {
+				payload.0 = State::Consumed;
+				match payload.1.take() {
+					Some(result) => Poll::Ready(Ok(result)),
+					None => Poll::Ready(Err(State::Consumed))
+				}
+			},
+			State::Waiting => {
+				payload.2 = Some(cx.waker().clone());
+				Poll::Pending
+			},
+			state => Poll::Ready(Err(state))
+		}
+	}
+}
 
 
 /// Computes the remaining time underflow-safe
@@ -212,6 +354,226 @@ pub fn run_async<T, F>(job: F) -> Future<T, ()>
 }
 
 
+/// Combines several futures into one that becomes ready once *every* input is ready
+///
+/// The results are collected in input order; if any input is canceled the returned future is
+/// canceled as well
+pub fn join<T, U>(futures: Vec<Future<T, U>>) -> Future<Vec<T>, ()>
+	where T: 'static + Send, U: 'static + Send
+{
+	run_async(move |dst: Future<Vec<T>>| {
+		let mut results = Vec::with_capacity(futures.len());
+		for future in futures {
+			match future.get() {
+				Ok(value) => results.push(value),
+				Err(_) => return dst.cancel()
+			}
+		}
+		let _ = dst.set(results);
+	})
+}
+
+/// Combines several futures into one that becomes ready with the index and value of the *first*
+/// input to become ready
+///
+/// If every input is canceled the returned future is canceled as well
+pub fn select<T, U>(futures: Vec<Future<T, U>>) -> Future<(usize, T), ()>
+	where T: 'static + Send, U: 'static + Send
+{
+	// Let all inputs race to `set` a single shared result future; the first winner wins and the
+	// losers' `set` simply fails. Nothing joins the loser threads, so an input that never resolves
+	// does not block the caller.
+	let dst = Future::<(usize, T)>::new();
+	if futures.is_empty() {
+		dst.cancel();
+		return dst
+	}
+	let remaining = Arc::new(AtomicUsize::new(futures.len()));
+	for (index, future) in futures.into_iter().enumerate() {
+		let dst = dst.clone();
+		let remaining = remaining.clone();
+		thread::spawn(move || {
+			if let Ok(value) = future.get() {
+				let _ = dst.set((index, value));
+			}
+			// The last input to finish without a winner cancels the result
+			if remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+				dst.cancel()
+			}
+		});
+	}
+	dst
+}
+
+
+/// The shared work queue behind a `Pool`
+struct PoolShared {
+	queue: Mutex<VecDeque<Box<dyn FnOnce() + Send>>>,
+	cond_var: Condvar,
+	is_shutdown: AtomicBool
+}
+
+/// A fixed-size pool of worker threads that run `job`s without spawning a thread per job
+///
+/// This is a cheaper alternative to `run_async`/`run_async_with_state` for server-style workloads
+/// that dispatch many short jobs
+pub struct Pool {
+	shared: Arc<PoolShared>,
+	workers: Vec<thread::JoinHandle<()>>
+}
+impl Pool {
+	/// Creates a new `Pool` with `num_threads` worker threads
+	pub fn new(num_threads: usize) -> Self {
+		let shared = Arc::new(PoolShared {
+			queue: Mutex::new(VecDeque::new()),
+			cond_var: Condvar::new(),
+			is_shutdown: AtomicBool::new(false)
+		});
+
+		// Spawn the workers
+		let mut workers = Vec::with_capacity(num_threads);
+		for _ in 0..num_threads {
+			let shared = shared.clone();
+			workers.push(thread::spawn(move || Self::work(shared)));
+		}
+		Pool{ shared, workers }
+	}
+
+	/// Creates a future for `job` and enqueues `job` onto the pool
+	///
+	/// The result of `job` will be set as result into the future; see `run_async_with_state` for the
+	/// semantics of the parameter passed to `job`
+	pub fn run_async_with_state<T, U, F>(&self, job: F, shared_state: U) -> Future<T, U>
+		where T: 'static + Send, U: 'static + Send, F: FnOnce(Future<T, U>) + Send + 'static
+	{
+		// Create future and enqueue job; cancel the future if the job panics so nobody blocks forever
+		let future = Future::with_state(shared_state);
+		let _future = future.clone();
+		self.shared.queue.lock().unwrap().push_back(Box::new(move || {
+			if panic::catch_unwind(AssertUnwindSafe(|| job(_future.clone()))).is_err() {
+				_future.cancel()
+			}
+		}));
+		self.shared.cond_var.notify_one();
+
+		future
+	}
+	/// Creates a future for `job` and enqueues `job` onto the pool
+	pub fn run_async<T, F>(&self, job: F) -> Future<T, ()>
+		where T: 'static + Send, F: FnOnce(Future<T, ()>) + Send + 'static
+	{
+		self.run_async_with_state(job, ())
+	}
+
+	/// The worker loop: pop and run jobs until the pool is shut down and the queue is drained
+	fn work(shared: Arc<PoolShared>) {
+		loop {
+			// Wait for a job or for shutdown
+			let job = {
+				let mut queue = shared.queue.lock().unwrap();
+				loop {
+					if let Some(job) = queue.pop_front() {
+						break Some(job)
+					} else if shared.is_shutdown.load(Ordering::SeqCst) {
+						break None
+					}
+					queue = shared.cond_var.wait(queue).unwrap();
+				}
+			};
+			match job {
+				// Isolate panics so a faulty job only loses its own future and the worker keeps serving
+				Some(job) => { let _ = panic::catch_unwind(AssertUnwindSafe(job)); },
+				None => return
+			}
+		}
+	}
+}
+impl Drop for Pool {
+	fn drop(&mut self) {
+		// Drain the pending queue and signal the workers to exit; publish the flag under the queue
+		// lock so it is visible to a worker about to re-check it after being notified
+		{
+			let mut queue = self.shared.queue.lock().unwrap();
+			queue.clear();
+			self.shared.is_shutdown.store(true, Ordering::SeqCst);
+		}
+		self.shared.cond_var.notify_all();
+		for worker in self.workers.drain(..) {
+			let _ = worker.join();
+		}
+	}
+}
+
+
+/// An expiring, single-flight cache built on top of `Future`
+///
+/// `Cached` coalesces concurrent requests for the same value: the provider runs only once while
+/// callers block on a shared `Future`, and the value is refreshed once `valid` reports it as stale
+pub struct Cached<T, F> {
+	slot: Arc<Mutex<Option<Future<T, ()>>>>,
+	provider: Arc<F>,
+	valid: fn(&T) -> bool
+}
+impl<T, F> Cached<T, F>
+	where T: Clone + Send + 'static, F: Fn() -> T + Send + Sync + 'static
+{
+	/// Creates a new `Cached` that produces values via `provider` and considers them fresh while
+	/// `valid` returns `true`
+	pub fn new(provider: F, valid: fn(&T) -> bool) -> Self {
+		Cached{ slot: Arc::new(Mutex::new(None)), provider: Arc::new(provider), valid }
+	}
+
+	/// Returns a fresh value, starting a refresh only if none is in flight and the cached value is
+	/// stale or absent
+	pub fn get(&self) -> Result<T, State> {
+		// Decide without blocking whether to reuse the current slot or to start exactly one refresh
+		let future = {
+			let mut slot = self.slot.lock().unwrap();
+			let reuse = match slot.as_ref() {
+				// A refresh is already in flight: join it
+				Some(future) if future.is_waiting() => true,
+				// A completed refresh: reuse it only while its value is still valid
+				Some(future) => future.peek(|value| (self.valid)(value)).unwrap_or(false),
+				None => false
+			};
+			if !reuse {
+				let provider = self.provider.clone();
+				// Guard against a panicking provider so the shared future is always driven out of
+				// `Waiting` — otherwise every caller blocked on it would deadlock forever
+				*slot = Some(run_async(move |future: Future<T>| {
+					match panic::catch_unwind(AssertUnwindSafe(|| provider())) {
+						Ok(value) => { let _ = future.set(value); },
+						Err(_) => future.cancel()
+					}
+				}));
+			}
+			slot.as_ref().unwrap().clone()
+		};
+
+		// Block on the shared future outside the slot lock and hand back a clone of its value
+		match future.get_cloned() {
+			Ok(value) => Ok(value),
+			Err(state) => {
+				// A failed/canceled refresh must not stick around so the next caller retries
+				let mut slot = self.slot.lock().unwrap();
+				let stale = slot.as_ref()
+					.map(|f| !f.is_waiting() && !f.peek(|value| (self.valid)(value)).unwrap_or(false))
+					.unwrap_or(false);
+				if stale {
+					*slot = None
+				}
+				Err(state)
+			}
+		}
+	}
+}
+impl<T, F> Clone for Cached<T, F> {
+	fn clone(&self) -> Self {
+		Cached{ slot: self.slot.clone(), provider: self.provider.clone(), valid: self.valid }
+	}
+}
+
+
 /// Sets `$result` as the `$future`'s result and returns
 #[macro_export]
 macro_rules! job_return {
@@ -268,6 +630,47 @@ mod test {
 		assert_eq!(fut.get().unwrap_err(), State::Canceled)
 	}
 	
+	#[test]
+	fn pool_runs_jobs() {
+		let pool = Pool::new(2);
+		let fut = pool.run_async(|fut: Future<u8>| {
+			thread::sleep(Duration::from_secs(1));
+			fut.set(7).unwrap();
+		});
+		assert_eq!(fut.get().unwrap(), 7);
+	}
+
+	#[test]
+	fn map_chains_result() {
+		let fut = run_async(|fut: Future<u8>| fut.set(7).unwrap());
+		assert_eq!(fut.map(|v| v as u16 * 2).get().unwrap(), 14);
+	}
+
+	#[test]
+	fn join_collects_in_order() {
+		let futures = vec![
+			run_async(|fut: Future<u8>| fut.set(1).unwrap()),
+			run_async(|fut: Future<u8>| {
+				thread::sleep(Duration::from_secs(1));
+				fut.set(2).unwrap();
+			}),
+			run_async(|fut: Future<u8>| fut.set(3).unwrap())
+		];
+		assert_eq!(join(futures).get().unwrap(), vec![1, 2, 3]);
+	}
+
+	#[test]
+	fn select_returns_first() {
+		let futures = vec![
+			run_async(|fut: Future<u8>| {
+				thread::sleep(Duration::from_secs(4));
+				fut.set(1).unwrap();
+			}),
+			run_async(|fut: Future<u8>| fut.set(2).unwrap())
+		];
+		assert_eq!(select(futures).get().unwrap(), (1, 2));
+	}
+
 	#[test]
 	fn is_ready_and_get() {
 		let fut = run_async(|fut: Future<u8>| {